@@ -0,0 +1,247 @@
+//! An async, non-blocking counterpart to `connection::Connection`, built on
+//! tokio's `AsyncRead`/`AsyncWrite`.
+//!
+//! `PacketCodec` composes with the compression and AES-128/CFB8 layers from
+//! `connection`: it buffers partial frames (the length prefix or body may
+//! arrive split across multiple reads) and only hands a fully deframed,
+//! decompressed packet body to the caller. `AsyncEncryptedStream` applies
+//! the same CFB8 cipher as `connection::EncryptedStream`, but over an
+//! `AsyncRead`/`AsyncWrite` pair instead of a blocking one, so a connection
+//! can be switched to encrypted transport before being framed with
+//! `connect`.
+
+use std::io;
+
+use bytes::{Buf, BufMut, BytesMut};
+use futures::Poll;
+use openssl::symm::{Cipher, Crypter, Mode};
+use tokio_io::codec::{Decoder, Encoder, Framed};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use connection::Packet;
+use errors::{Error, Result};
+use read::MAX_PACKET_LEN;
+
+/// A `tokio_io::codec::Decoder`/`Encoder` that frames packet bodies onto and
+/// off of a byte stream exactly like `connection::Packet`, but in a
+/// non-blocking, partial-read-tolerant fashion suitable for use with
+/// `Framed`.
+pub struct PacketCodec {
+    compression_threshold: i32,
+}
+
+impl PacketCodec {
+    pub fn new() -> Self {
+        PacketCodec { compression_threshold: -1 }
+    }
+
+    pub fn set_compression_threshold(&mut self, threshold: i32) {
+        self.compression_threshold = threshold;
+    }
+}
+
+impl Decoder for PacketCodec {
+    type Item = Packet;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>> {
+        let (len, prefix_len) = match try_read_varint(src) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if len < 0 || len as usize > MAX_PACKET_LEN {
+            bail!("Peer declared an invalid packet length ({})", len);
+        }
+        let len = len as usize;
+
+        if src.len() < prefix_len + len {
+            // Not all of the body has arrived yet; wait for more data
+            // without consuming what's already buffered.
+            return Ok(None);
+        }
+
+        src.advance(prefix_len);
+        // `body` has already had the outer VarInt(length) prefix stripped
+        // off above, so it must go through `from_body`, not `from_reader`
+        // (which would try to read a second length prefix off the front of
+        // it and corrupt every packet).
+        let body = src.split_to(len);
+        Packet::from_body(body.to_vec(), self.compression_threshold).map(Some)
+    }
+}
+
+impl Encoder for PacketCodec {
+    type Item = Vec<u8>;
+    type Error = Error;
+
+    fn encode(&mut self, item: Vec<u8>, dst: &mut BytesMut) -> Result<()> {
+        let bytes = Packet::new(item).to_u8(self.compression_threshold)?;
+        dst.reserve(bytes.len());
+        dst.put_slice(&bytes);
+        Ok(())
+    }
+}
+
+/// Try to decode a VarInt from the front of `buf` without consuming
+/// anything. Returns `None` if `buf` doesn't yet contain a complete VarInt
+/// (either too short, or not yet terminated within 5 bytes).
+fn try_read_varint(buf: &[u8]) -> Option<(i32, usize)> {
+    let mut result: i32 = 0;
+    for (i, &byte) in buf.iter().enumerate().take(5) {
+        result |= ((byte & 0b0111_1111) as i32) << (7 * i);
+        if byte & 0b1000_0000 == 0 {
+            return Some((result, i + 1));
+        }
+    }
+    None
+}
+
+/// Wraps `stream` in the packet-framing codec, yielding a `Stream` of
+/// decoded `Packet`s to read and a `Sink` of raw packet bodies to write.
+/// `codec`'s compression threshold can be changed later through the
+/// `Framed`'s `codec_mut`.
+pub fn connect<T: AsyncRead + AsyncWrite>(stream: T) -> Framed<T, PacketCodec> {
+    Framed::new(stream, PacketCodec::new())
+}
+
+/// Wraps an inner async stream, transparently encrypting every outgoing
+/// byte and decrypting every incoming byte with AES-128 in CFB8 mode, the
+/// same way `connection::EncryptedStream` does for blocking streams.
+///
+/// Unlike the blocking wrapper, `write` here may be called again with the
+/// same plaintext after a `WouldBlock`, so already-encrypted-but-not-yet-
+/// sent bytes are kept in `pending_ciphertext` rather than being re-derived
+/// from the plaintext: CFB8 is a streaming cipher and `encrypt` cannot be
+/// "rewound", so a byte must only ever be fed into it once.
+pub struct AsyncEncryptedStream<T> {
+    inner: T,
+    encrypt: Crypter,
+    decrypt: Crypter,
+    pending_ciphertext: Vec<u8>,
+}
+
+impl<T> AsyncEncryptedStream<T> {
+    pub fn new(inner: T, shared_secret: &[u8; 16]) -> Result<Self> {
+        let cipher = Cipher::aes_128_cfb8();
+        let mut encrypt = Crypter::new(cipher, Mode::Encrypt, shared_secret, Some(shared_secret))?;
+        let mut decrypt = Crypter::new(cipher, Mode::Decrypt, shared_secret, Some(shared_secret))?;
+        encrypt.pad(false);
+        decrypt.pad(false);
+        Ok(AsyncEncryptedStream {
+               inner: inner,
+               encrypt: encrypt,
+               decrypt: decrypt,
+               pending_ciphertext: Vec::new(),
+           })
+    }
+}
+
+impl<T: io::Write> AsyncEncryptedStream<T> {
+    /// Push as much of the ciphertext buffered by a previous `write` call
+    /// as the inner stream will currently accept. Must fully drain before
+    /// any new plaintext is fed into `encrypt`.
+    fn flush_pending(&mut self) -> io::Result<()> {
+        while !self.pending_ciphertext.is_empty() {
+            let n = self.inner.write(&self.pending_ciphertext)?;
+            if n == 0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                           "failed to write buffered ciphertext"));
+            }
+            self.pending_ciphertext.drain(..n);
+        }
+        Ok(())
+    }
+}
+
+impl<T: io::Read> io::Read for AsyncEncryptedStream<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            let mut decrypted = vec![0; n + Cipher::aes_128_cfb8().block_size()];
+            let count = self.decrypt
+                .update(&buf[..n], &mut decrypted)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            buf[..count].copy_from_slice(&decrypted[..count]);
+        }
+        Ok(n)
+    }
+}
+
+impl<T: io::Write> io::Write for AsyncEncryptedStream<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Any ciphertext left over from a previous write must be fully
+        // flushed before `buf` is encrypted -- otherwise a caller retrying
+        // this write after a `WouldBlock` would get re-encrypted into
+        // different ciphertext than what the peer's decryptor expects.
+        self.flush_pending()?;
+
+        let mut encrypted = vec![0; buf.len() + Cipher::aes_128_cfb8().block_size()];
+        let count = self.encrypt
+            .update(buf, &mut encrypted)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        encrypted.truncate(count);
+        self.pending_ciphertext = encrypted;
+
+        match self.flush_pending() {
+            Ok(()) => Ok(buf.len()),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(buf.len()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_pending()?;
+        self.inner.flush()
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for AsyncEncryptedStream<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codec_roundtrips_a_packet() {
+        let mut codec = PacketCodec::new();
+        let mut buf = BytesMut::new();
+        codec.encode(vec![1, 2, 3], &mut buf).unwrap();
+
+        let packet = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(packet.data, vec![1, 2, 3]);
+        // The whole frame was consumed, nothing left buffered.
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn codec_roundtrips_with_compression_enabled() {
+        let mut codec = PacketCodec::new();
+        codec.set_compression_threshold(1);
+        let mut buf = BytesMut::new();
+        codec.encode(vec![42; 128], &mut buf).unwrap();
+
+        let packet = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(packet.data, vec![42; 128]);
+    }
+
+    #[test]
+    fn codec_waits_for_a_full_frame_before_decoding() {
+        let mut codec = PacketCodec::new();
+        let mut buf = BytesMut::new();
+        codec.encode(vec![1, 2, 3, 4, 5], &mut buf).unwrap();
+
+        // Split the encoded frame so only part of the body has arrived.
+        let second_half = buf.split_off(buf.len() - 2);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+
+        buf.unsplit(second_half);
+        let packet = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(packet.data, vec![1, 2, 3, 4, 5]);
+    }
+}
+
+impl<T: AsyncWrite> AsyncWrite for AsyncEncryptedStream<T> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
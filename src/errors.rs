@@ -0,0 +1,11 @@
+//! Error types for this crate, built with `error_chain`
+
+error_chain! {
+    foreign_links {
+        Io(::std::io::Error);
+        Utf8(::std::string::FromUtf8Error);
+        OpenSsl(::openssl::error::ErrorStack);
+        Json(::serde_json::Error);
+        Http(::reqwest::Error);
+    }
+}
@@ -0,0 +1,42 @@
+//! ozelot: A Rust library for writing Minecraft clients, servers and proxies
+//!
+//! See the individual modules for documentation on how to use this crate.
+
+#![recursion_limit = "1024"]
+
+extern crate byteorder;
+extern crate bytes;
+#[macro_use]
+extern crate error_chain;
+extern crate flate2;
+extern crate futures;
+extern crate openssl;
+extern crate reqwest;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate tokio_io;
+extern crate uuid;
+
+pub mod async_io;
+pub mod connection;
+pub mod errors;
+pub mod mojang;
+pub mod read;
+pub mod status;
+pub mod write;
+pub mod utils;
+pub mod serverbound;
+
+mod u128_impl;
+pub use u128_impl::u128;
+
+/// The state of a client/server connection, as described on
+/// http://wiki.vg/Protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientState {
+    Handshake,
+    Status,
+    Login,
+    Play,
+}
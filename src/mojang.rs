@@ -0,0 +1,177 @@
+//! Mojang account authentication (Yggdrasil) and the session server call
+//! needed to join online-mode servers.
+//!
+//! See http://wiki.vg/Authentication and
+//! http://wiki.vg/Protocol_Encryption#Authentication for details on the
+//! flows implemented here.
+
+use openssl::sha::sha1;
+use reqwest;
+
+use errors::Result;
+
+const AUTH_SERVER: &'static str = "https://authserver.mojang.com/authenticate";
+const SESSION_SERVER: &'static str = "https://sessionserver.mojang.com/session/minecraft/join";
+
+/// The result of a successful Yggdrasil authentication: an access token to
+/// keep around for the session join, and the authenticated profile.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthResponse {
+    #[serde(rename = "accessToken")]
+    pub access_token: String,
+    #[serde(rename = "selectedProfile")]
+    pub selected_profile: Profile,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+struct Agent {
+    name: &'static str,
+    version: u8,
+}
+
+#[derive(Serialize)]
+struct AuthRequest<'a> {
+    agent: Agent,
+    username: &'a str,
+    password: &'a str,
+}
+
+/// The error body Mojang's auth/session servers send back on a non-2xx
+/// response, e.g. for wrong credentials or rate-limiting.
+#[derive(Debug, Clone, Deserialize)]
+struct MojangError {
+    error: String,
+    #[serde(rename = "errorMessage")]
+    error_message: String,
+}
+
+/// Authenticate against Mojang's Yggdrasil auth server with a Mojang
+/// account's username and password, returning the access token and profile
+/// needed to join online-mode servers.
+pub fn authenticate(username: &str, password: &str) -> Result<AuthResponse> {
+    let req = AuthRequest {
+        agent: Agent {
+            name: "Minecraft",
+            version: 1,
+        },
+        username: username,
+        password: password,
+    };
+
+    let client = reqwest::Client::new();
+    let mut response = client.post(AUTH_SERVER).json(&req).send()?;
+    if !response.status().is_success() {
+        let err: MojangError = response.json()?;
+        bail!("Mojang auth server rejected the login: {} ({})",
+              err.error_message,
+              err.error);
+    }
+    Ok(response.json()?)
+}
+
+#[derive(Serialize)]
+struct JoinRequest<'a> {
+    #[serde(rename = "accessToken")]
+    access_token: &'a str,
+    #[serde(rename = "selectedProfile")]
+    selected_profile: &'a str,
+    #[serde(rename = "serverId")]
+    server_id: &'a str,
+}
+
+/// Notify the session server that `profile_id` (authenticated by
+/// `access_token`) is joining the server identified by `server_hash`, as
+/// computed by `compute_server_hash`. This must be called by the client
+/// before the EncryptionResponse is sent, as
+/// `EncryptionResponse::new_authenticated` does.
+pub fn join_server(access_token: &str, profile_id: &str, server_hash: &str) -> Result<()> {
+    let req = JoinRequest {
+        access_token: access_token,
+        selected_profile: profile_id,
+        server_id: server_hash,
+    };
+
+    let client = reqwest::Client::new();
+    let status = client.post(SESSION_SERVER).json(&req).send()?.status();
+    if !status.is_success() {
+        bail!("Session server rejected the join request, got status {}",
+              status);
+    }
+    Ok(())
+}
+
+/// Compute Minecraft's non-standard "server hash" (the `serverId` sent to
+/// the session server): SHA-1 over the ASCII server id from the
+/// EncryptionRequest, the raw 16-byte shared secret, and the server's
+/// public key in DER form.
+pub fn compute_server_hash(server_id: &str, shared_secret: &[u8], public_key_der: &[u8]) -> String {
+    let mut input = Vec::new();
+    input.extend_from_slice(server_id.as_bytes());
+    input.extend_from_slice(shared_secret);
+    input.extend_from_slice(public_key_der);
+
+    minecraft_sha1_hex_digest(&sha1(&input))
+}
+
+/// Interpret a 20-byte SHA-1 digest as a signed, big-endian two's-complement
+/// integer and format it the way Mojang's servers expect it: plain hex if
+/// positive, or a `-` followed by the hex of the negated value if the top
+/// bit of the digest is set. Leading zeroes are stripped, keeping at least
+/// one digit (and the sign, if any).
+fn minecraft_sha1_hex_digest(digest: &[u8; 20]) -> String {
+    let negative = digest[0] & 0x80 != 0;
+    let mut digest = *digest;
+    if negative {
+        two_complement_negate(&mut digest);
+    }
+
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    let trimmed = hex.trim_start_matches('0');
+    let trimmed = if trimmed.is_empty() { "0" } else { trimmed };
+
+    if negative {
+        format!("-{}", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Negate a big-endian two's-complement integer in place.
+fn two_complement_negate(bytes: &mut [u8; 20]) {
+    let mut carry = 1u16;
+    for byte in bytes.iter_mut().rev() {
+        let inverted = !*byte as u16 + carry;
+        *byte = inverted as u8;
+        carry = inverted >> 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from http://wiki.vg/Protocol_Encryption#Authentication
+    #[test]
+    fn notch_hash() {
+        assert_eq!(minecraft_sha1_hex_digest(&sha1(b"Notch")),
+                   "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48");
+    }
+
+    #[test]
+    fn jeb_hash() {
+        assert_eq!(minecraft_sha1_hex_digest(&sha1(b"jeb_")),
+                   "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1");
+    }
+
+    #[test]
+    fn simon_hash() {
+        assert_eq!(minecraft_sha1_hex_digest(&sha1(b"simon")),
+                   "88e16a1019277b15d58faf0541e11910eb756f6");
+    }
+}
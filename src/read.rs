@@ -0,0 +1,94 @@
+//! Functions for reading the primitive types used by the Minecraft protocol
+//! off of a `Read`, as described on http://wiki.vg/Protocol
+
+#![allow(non_snake_case)]
+
+use std::io::Read;
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use errors::Result;
+
+/// The protocol caps a single packet at this many bytes (see
+/// http://wiki.vg/Protocol#Packet_format). `read_byte_array` rejects any
+/// declared length beyond it before allocating, so a malformed or hostile
+/// VarInt length prefix (including a negative one, which wraps to a huge
+/// `usize` on cast) can't be used to force a multi-gigabyte allocation.
+pub const MAX_PACKET_LEN: usize = 2_097_151; // 2^21 - 1
+
+/// Strings are capped at 32767 UTF-16 code units by the protocol; a UTF-8
+/// encoding of one code unit is at most 4 bytes.
+const MAX_STRING_LEN: usize = 32767 * 4;
+
+pub fn read_varint<R: Read>(r: &mut R) -> Result<i32> {
+    let mut result: i32 = 0;
+    let mut num_read = 0;
+    loop {
+        if num_read == 5 {
+            bail!("VarInt was more than 5 bytes long");
+        }
+        let byte = read_u8(r)?;
+        let value = (byte & 0b0111_1111) as i32;
+        result |= value << (7 * num_read);
+
+        num_read += 1;
+        if byte & 0b1000_0000 == 0 {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+pub fn read_u8<R: Read>(r: &mut R) -> Result<u8> {
+    Ok(r.read_u8()?)
+}
+
+pub fn read_bool<R: Read>(r: &mut R) -> Result<bool> {
+    Ok(read_u8(r)? != 0)
+}
+
+pub fn read_f32<R: Read>(r: &mut R) -> Result<f32> {
+    Ok(r.read_f32::<BigEndian>()?)
+}
+
+pub fn read_i32<R: Read>(r: &mut R) -> Result<i32> {
+    Ok(r.read_i32::<BigEndian>()?)
+}
+
+pub fn read_i64<R: Read>(r: &mut R) -> Result<i64> {
+    Ok(r.read_i64::<BigEndian>()?)
+}
+
+#[allow(non_snake_case)]
+pub fn read_String<R: Read>(r: &mut R) -> Result<String> {
+    let len = read_varint(r)? as usize;
+    if len > MAX_STRING_LEN {
+        bail!("Refusing to read a {}-byte String, maximum is {} bytes",
+              len,
+              MAX_STRING_LEN);
+    }
+    let buf = read_byte_array(r, len)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Reads a block position packed into a 64-bit integer, as described on
+/// http://wiki.vg/Protocol#Position
+pub fn read_position<R: Read>(r: &mut R) -> Result<(i32, i32, i32)> {
+    let val = read_i64(r)?;
+    let x = (val >> 38) as i32;
+    let y = ((val >> 26) & 0xFFF) as i32;
+    let z = (val << 38 >> 38) as i32;
+    Ok((x, y, z))
+}
+
+pub fn read_byte_array<R: Read>(r: &mut R, len: usize) -> Result<Vec<u8>> {
+    if len > MAX_PACKET_LEN {
+        bail!("Refusing to allocate a {}-byte buffer, maximum packet \
+                length is {} bytes",
+              len,
+              MAX_PACKET_LEN);
+    }
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
@@ -5,6 +5,7 @@
 
 use connection::Packet;
 use errors::Result;
+use mojang;
 use read::*;
 use write::*;
 use {ClientState, u128, utils};
@@ -217,4 +218,22 @@ impl EncryptionResponse {
 
         Ok(EncryptionResponse::new(ss_encrypted, verify_encrypted))
     }
+
+    /// Like `new_unencrypted`, but also performs the online-mode session
+    /// join against Mojang's session server before the EncryptionResponse
+    /// is returned, as required to complete login against online-mode
+    /// servers. `server_id` and `key` come from the preceding
+    /// EncryptionRequest.
+    pub fn new_authenticated(key: &[u8],
+                             shared_secret: &[u8],
+                             verify_token: &[u8],
+                             server_id: &str,
+                             access_token: &str,
+                             profile_id: &str)
+                             -> Result<ServerboundPacket> {
+        let hash = mojang::compute_server_hash(server_id, shared_secret, key);
+        mojang::join_server(access_token, profile_id, &hash)?;
+
+        EncryptionResponse::new_unencrypted(key, shared_secret, verify_token)
+    }
 }
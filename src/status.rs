@@ -0,0 +1,90 @@
+//! Typed representations of the JSON a server sends back in response to a
+//! `StatusRequest`, as described on http://wiki.vg/Server_List_Ping.
+
+use serde_json;
+
+use errors::Result;
+
+/// The full status response, as sent by the server right after the
+/// `Handshake`/`StatusRequest` pair.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatusResponse {
+    pub version: Version,
+    pub players: Players,
+    pub description: Description,
+    pub favicon: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Version {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Players {
+    pub max: i32,
+    pub online: i32,
+    #[serde(default)]
+    pub sample: Vec<PlayerSample>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayerSample {
+    pub name: String,
+    pub id: String,
+}
+
+/// The MOTD, which the protocol permits as either a plain string or a
+/// structured chat component object (see http://wiki.vg/Chat).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Description {
+    Plain(String),
+    Chat { text: String },
+}
+
+impl Description {
+    /// Get the text of this description, regardless of which form it was
+    /// sent in. For the chat component form this is only the component's
+    /// top-level `text`; `extra` children and other fields are not kept.
+    pub fn text(&self) -> &str {
+        match *self {
+            Description::Plain(ref s) => s,
+            Description::Chat { ref text } => text,
+        }
+    }
+}
+
+/// Parse the JSON body of a status response into a `StatusResponse`.
+pub fn parse(json: &str) -> Result<StatusResponse> {
+    Ok(serde_json::from_str(json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_plain_description() {
+        let json = r#"{
+            "version": {"name": "1.12.2", "protocol": 340},
+            "players": {"max": 20, "online": 1, "sample": []},
+            "description": "A Minecraft Server"
+        }"#;
+        let status = parse(json).unwrap();
+        assert_eq!(status.version.protocol, 340);
+        assert_eq!(status.description.text(), "A Minecraft Server");
+    }
+
+    #[test]
+    fn parses_chat_component_description() {
+        let json = r#"{
+            "version": {"name": "1.12.2", "protocol": 340},
+            "players": {"max": 20, "online": 0},
+            "description": {"text": "A Minecraft Server"}
+        }"#;
+        let status = parse(json).unwrap();
+        assert_eq!(status.description.text(), "A Minecraft Server");
+    }
+}
@@ -0,0 +1,15 @@
+//! A minimal 128-bit unsigned integer, used for fields such as entity UUIDs
+//! in the generated packet definitions, on targets predating a native
+//! `u128` primitive.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct u128 {
+    pub hi: u64,
+    pub lo: u64,
+}
+
+impl u128 {
+    pub fn new(hi: u64, lo: u64) -> Self {
+        u128 { hi: hi, lo: lo }
+    }
+}
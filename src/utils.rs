@@ -0,0 +1,24 @@
+//! Miscellaneous helper functions used throughout the crate
+
+use openssl::rsa::{Padding, Rsa};
+
+use errors::Result;
+
+/// Encrypt `data` with the given RSA public key (in DER format), using
+/// PKCS1 padding as required by the protocol's encryption handshake.
+pub fn rsa_encrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let rsa = Rsa::public_key_from_der(key)?;
+    let mut buf = vec![0; rsa.size() as usize];
+    let len = rsa.public_encrypt(data, &mut buf, Padding::PKCS1)?;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+/// Decrypt `data` with the given RSA private key, using PKCS1 padding as
+/// required by the protocol's encryption handshake.
+pub fn rsa_decrypt(key: &Rsa, data: &[u8]) -> Result<Vec<u8>> {
+    let mut buf = vec![0; key.size() as usize];
+    let len = key.private_decrypt(data, &mut buf, Padding::PKCS1)?;
+    buf.truncate(len);
+    Ok(buf)
+}
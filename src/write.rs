@@ -0,0 +1,63 @@
+//! Functions for writing the primitive types used by the Minecraft protocol
+//! onto a `Write`, as described on http://wiki.vg/Protocol
+
+#![allow(non_snake_case)]
+
+use std::io::Write;
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use errors::Result;
+
+pub fn write_varint<W: Write>(val: &i32, w: &mut W) -> Result<()> {
+    let mut val = *val as u32;
+    loop {
+        let mut byte = (val & 0b0111_1111) as u8;
+        val >>= 7;
+        if val != 0 {
+            byte |= 0b1000_0000;
+        }
+        w.write_u8(byte)?;
+        if val == 0 {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_bool<W: Write>(val: &bool, w: &mut W) -> Result<()> {
+    Ok(w.write_u8(if *val { 1 } else { 0 })?)
+}
+
+pub fn write_f32<W: Write>(val: &f32, w: &mut W) -> Result<()> {
+    Ok(w.write_f32::<BigEndian>(*val)?)
+}
+
+pub fn write_i32<W: Write>(val: &i32, w: &mut W) -> Result<()> {
+    Ok(w.write_i32::<BigEndian>(*val)?)
+}
+
+pub fn write_i64<W: Write>(val: &i64, w: &mut W) -> Result<()> {
+    Ok(w.write_i64::<BigEndian>(*val)?)
+}
+
+#[allow(non_snake_case)]
+pub fn write_String<W: Write>(val: &str, w: &mut W) -> Result<()> {
+    write_varint(&(val.len() as i32), w)?;
+    w.write_all(val.as_bytes())?;
+    Ok(())
+}
+
+/// Writes a block position packed into a 64-bit integer, as described on
+/// http://wiki.vg/Protocol#Position
+pub fn write_position<W: Write>(val: &(i32, i32, i32), w: &mut W) -> Result<()> {
+    let (x, y, z) = *val;
+    let packed = ((x as i64 & 0x3FF_FFFF) << 38) | ((y as i64 & 0xFFF) << 26) |
+        (z as i64 & 0x3FF_FFFF);
+    write_i64(&packed, w)
+}
+
+pub fn write_byte_array<W: Write>(val: &[u8], w: &mut W) -> Result<()> {
+    w.write_all(val)?;
+    Ok(())
+}